@@ -6,11 +6,10 @@ use std::{
 };
 
 use anyhow::{Context, Result};
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, Parser, ValueEnum};
 use directories::{ProjectDirs, UserDirs};
 use env_logger::Env;
 use futures_util::StreamExt;
-use id3::{Tag, TagLike, Version, frame::PictureType};
 use indicatif::{ProgressBar, ProgressStyle};
 use librespot::{
     audio::{AudioDecrypt, AudioFile as SpotifyAudioFile},
@@ -21,7 +20,15 @@ use librespot::{
         spotify_id::SpotifyId,
     },
     discovery::{Credentials, Discovery},
-    metadata::{Metadata, Track, audio::AudioFileFormat},
+    metadata::{Episode, Metadata, Restriction, Track, audio::AudioFileFormat},
+};
+use lofty::{
+    config::WriteOptions,
+    file::TaggedFileExt,
+    picture::{MimeType, Picture, PictureType},
+    prelude::Accessor,
+    probe::Probe,
+    tag::{ItemKey, Tag},
 };
 use log::{LevelFilter, debug, error, info, warn};
 use reqwest::blocking::Client;
@@ -33,11 +40,15 @@ use url::Url;
 const DEVICE_NAME: &str = "Mr. Rippah";
 const SPOTIFY_MARKET: &str = "US";
 const SUCCESSFUL_DOWNLOAD_DELAY_SECONDS: u64 = 5;
+const SPOTIFY_API_MAX_RETRIES: u32 = 5;
+const SPOTIFY_RATE_LIMIT_DEFAULT_DELAY_SECONDS: u64 = 5;
+const CONFIG_FILE_NAME: &str = "config.toml";
+const DEFAULT_PATH_TEMPLATE: &str = "{artist}/{album}/{track_number} - {title}";
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "Download Spotify playlists using librespot")]
+#[command(author, version, about = "Download Spotify tracks, albums, artists, playlists, and podcasts using librespot")]
 struct Cli {
-    /// Spotify playlist URI or URL
+    /// Spotify track, album, artist, playlist, episode, or show URI or URL
     uri: String,
 
     /// Clear existing cached Spotify credentials
@@ -51,6 +62,103 @@ struct Cli {
     /// Suppress non-error output
     #[arg(short, long, action = ArgAction::Count)]
     quiet: u8,
+
+    /// Audio quality/format preset to request from Spotify (overrides the config file)
+    #[arg(long, value_enum)]
+    quality: Option<QualityPreset>,
+
+    /// Number of tracks/episodes to download concurrently (overrides the config file)
+    #[arg(long)]
+    concurrency: Option<usize>,
+
+    /// Path to a TOML config file (defaults to the platform config directory)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Base directory downloads are written under (overrides the config file)
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+
+    /// Output path/filename template, relative to the output directory
+    /// (overrides the config file). Supports `{artist}`, `{album}`,
+    /// `{track_number}`, `{title}`, `{disc}`, and `{year}`.
+    #[arg(long)]
+    path_template: Option<String>,
+}
+
+/// Selects which Spotify audio formats to request and how they're delivered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+enum QualityPreset {
+    /// OGG Vorbis only, written straight to disk with no re-encode.
+    OggOnly,
+    /// MP3 only, transcoding via ffmpeg when Spotify doesn't deliver MP3 natively.
+    Mp3Only,
+    /// FLAC only, written straight to disk with no re-encode.
+    Flac,
+    /// The highest-bitrate FLAC, OGG Vorbis, or MP3 format Spotify offers, kept in its
+    /// native container. AAC and MP4 formats are not requested.
+    BestBitrate,
+}
+
+impl QualityPreset {
+    /// Ordered list of formats to request from `track.files`, best first.
+    fn format_priority(self) -> &'static [AudioFileFormat] {
+        use AudioFileFormat::*;
+        match self {
+            QualityPreset::OggOnly => &[OGG_VORBIS_320, OGG_VORBIS_160, OGG_VORBIS_96],
+            // Spotify doesn't actually serve MP3 to librespot clients, so request
+            // the best OGG Vorbis source available and let `finalize_audio`
+            // transcode it to MP3 via ffmpeg.
+            QualityPreset::Mp3Only => &[OGG_VORBIS_320, OGG_VORBIS_160, OGG_VORBIS_96],
+            QualityPreset::Flac => &[FLAC_FLAC_24BIT, FLAC_FLAC],
+            QualityPreset::BestBitrate => &[
+                FLAC_FLAC_24BIT,
+                FLAC_FLAC,
+                OGG_VORBIS_320,
+                MP3_320,
+                MP3_256,
+                OGG_VORBIS_160,
+                MP3_160,
+                OGG_VORBIS_96,
+                MP3_96,
+            ],
+        }
+    }
+
+    /// The file extension this preset wants to deliver, given the format that was
+    /// actually available. `BestBitrate` keeps whatever native container it got;
+    /// the other presets pin a specific delivery format.
+    fn delivery_extension(self, format: AudioFileFormat) -> &'static str {
+        match self {
+            QualityPreset::OggOnly => "ogg",
+            QualityPreset::Mp3Only => "mp3",
+            QualityPreset::Flac => "flac",
+            QualityPreset::BestBitrate => native_extension(format),
+        }
+    }
+}
+
+/// The container extension a Spotify `AudioFileFormat` is natively delivered in.
+fn native_extension(format: AudioFileFormat) -> &'static str {
+    use AudioFileFormat::*;
+    match format {
+        OGG_VORBIS_96 | OGG_VORBIS_160 | OGG_VORBIS_320 => "ogg",
+        MP3_96 | MP3_160 | MP3_160_ENC | MP3_256 | MP3_320 => "mp3",
+        FLAC_FLAC | FLAC_FLAC_24BIT => "flac",
+        AAC_24 | AAC_48 | AAC_160 | AAC_320 | XHE_AAC_12 | XHE_AAC_16 | XHE_AAC_24 => "m4a",
+        MP4_128 => "mp4",
+        OTHER5 => "bin",
+    }
+}
+
+/// Parses the leading four-digit year out of a Spotify `release_date`
+/// (`"YYYY"`, `"YYYY-MM"`, or `"YYYY-MM-DD"`). Returns `None` rather than
+/// panicking when the string is empty, shorter than expected, or does not
+/// start with a number.
+fn parse_release_year(release_date: &str) -> Option<u32> {
+    release_date.get(0..4)?.parse::<u32>().ok()
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -69,6 +177,88 @@ struct PlaylistTrack {
     id: Option<String>,
 }
 
+/// The kind of Spotify resource a URI/URL points at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ResourceKind {
+    Playlist,
+    Album,
+    Artist,
+    Track,
+    Episode,
+    Show,
+}
+
+impl ResourceKind {
+    const ALL: [ResourceKind; 6] = [
+        ResourceKind::Playlist,
+        ResourceKind::Album,
+        ResourceKind::Artist,
+        ResourceKind::Track,
+        ResourceKind::Episode,
+        ResourceKind::Show,
+    ];
+
+    /// The lowercase segment used in both `spotify:<segment>:<id>` URIs and
+    /// `open.spotify.com/<segment>/<id>` URLs.
+    fn uri_segment(self) -> &'static str {
+        match self {
+            ResourceKind::Playlist => "playlist",
+            ResourceKind::Album => "album",
+            ResourceKind::Artist => "artist",
+            ResourceKind::Track => "track",
+            ResourceKind::Episode => "episode",
+            ResourceKind::Show => "show",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct AlbumTracksResponse {
+    items: Vec<SimplifiedTrack>,
+    next: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct SimplifiedTrack {
+    id: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ArtistAlbumsResponse {
+    items: Vec<SimplifiedAlbum>,
+    next: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct SimplifiedAlbum {
+    id: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ShowEpisodesResponse {
+    items: Vec<SimplifiedEpisode>,
+    next: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct SimplifiedEpisode {
+    id: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct EpisodeMetadata {
+    name: String,
+    is_playable: Option<bool>,
+    release_date: String,
+    show: ShowMetadata,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ShowMetadata {
+    name: String,
+    images: Vec<ImageMetadata>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 struct TrackMetadata {
     name: String,
@@ -103,14 +293,118 @@ struct ExternalIds {
     isrc: Option<String>,
 }
 
+/// On-disk defaults, loaded from a TOML config file. Every field is
+/// overridable by the matching CLI flag.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct RippahConfig {
+    quality: Option<QualityPreset>,
+    concurrency: Option<usize>,
+    output_dir: Option<PathBuf>,
+    path_template: Option<String>,
+}
+
+impl RippahConfig {
+    /// Loads config from `path`, or from the platform config directory if
+    /// `path` is `None`. Missing files are treated as an empty config rather
+    /// than an error, since the config file is always optional.
+    fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => default_config_path(),
+        };
+
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read config file at {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Unable to parse config file at {}", path.display()))
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    ProjectDirs::from("dev", "mr-rippah", "Mr Rippah")
+        .map(|dirs| dirs.config_dir().join(CONFIG_FILE_NAME))
+}
+
+/// The values a `--path-template` placeholder can expand to.
+struct PathTemplateVars<'a> {
+    artist: &'a str,
+    album: &'a str,
+    track_number: u32,
+    disc_number: u32,
+    title: &'a str,
+    year: Option<u32>,
+}
+
+/// Renders a `--path-template` string (e.g. `"{artist}/{album}/{track_number} - {title}"`)
+/// against a track/episode's metadata, sanitizing each interpolated value so
+/// it can't introduce path separators or other filesystem-illegal characters.
+fn render_path_template(template: &str, vars: &PathTemplateVars) -> String {
+    template
+        .replace("{artist}", &sanitize_path_component(vars.artist))
+        .replace("{album}", &sanitize_path_component(vars.album))
+        .replace("{title}", &sanitize_path_component(vars.title))
+        .replace("{track_number}", &format!("{:02}", vars.track_number))
+        .replace("{disc}", &vars.disc_number.to_string())
+        .replace(
+            "{year}",
+            &vars.year.map(|year| year.to_string()).unwrap_or_default(),
+        )
+}
+
+/// Replaces characters that are illegal in a path component on common
+/// filesystems with `_`, so an interpolated metadata value can't smuggle in a path
+/// separator or other special character.
+fn sanitize_path_component(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Metadata common to anything we can tag, whether it came from `tracks/` or
+/// `episodes/` metadata.
+struct AudioTagFields<'a> {
+    title: &'a str,
+    artist: &'a str,
+    album_artist: Option<&'a str>,
+    album: &'a str,
+    track_number: u32,
+    disc_number: u32,
+    year: Option<u32>,
+    isrc: Option<&'a str>,
+    source_uri: &'a str,
+    cover_art_url: Option<&'a str>,
+}
+
 struct MrRippah {
     session: Session,
-    downloads_dir: PathBuf,
+    output_dir: PathBuf,
+    path_template: String,
     http_client: Client,
+    quality_preset: QualityPreset,
+    concurrency: usize,
 }
 
 impl MrRippah {
-    async fn new(clear_credentials: bool, log_level: LevelFilter) -> Result<Self> {
+    async fn new(
+        clear_credentials: bool,
+        log_level: LevelFilter,
+        quality_preset: QualityPreset,
+        concurrency: usize,
+        output_dir: Option<PathBuf>,
+        path_template: String,
+    ) -> Result<Self> {
         let project_dirs = ProjectDirs::from("dev", "mr-rippah", "Mr Rippah")
             .context("Unable to determine cache directories")?;
         let cache_dir = project_dirs.cache_dir();
@@ -149,17 +443,23 @@ impl MrRippah {
 
         let session = Self::create_session(&cache, &session_config, log_level).await?;
 
-        let downloads_dir = UserDirs::new()
-            .and_then(|dirs| dirs.download_dir().map(|path| path.to_path_buf()))
-            .unwrap_or(std::env::current_dir().context("Unable to determine current directory")?);
+        let output_dir = match output_dir {
+            Some(output_dir) => output_dir,
+            None => UserDirs::new()
+                .and_then(|dirs| dirs.download_dir().map(|path| path.to_path_buf()))
+                .unwrap_or(std::env::current_dir().context("Unable to determine current directory")?),
+        };
 
         Ok(Self {
             session,
-            downloads_dir,
+            output_dir,
+            path_template,
             http_client: Client::builder()
                 .user_agent("Mr Rippah")
                 .build()
                 .context("Unable to build HTTP client")?,
+            quality_preset,
+            concurrency: concurrency.max(1),
         })
     }
 
@@ -222,18 +522,46 @@ impl MrRippah {
         Ok(credentials)
     }
 
-    async fn rip_playlist(&self, playlist_uri: &str) -> Result<()> {
-        let playlist_uri = Self::normalise_playlist_uri(playlist_uri)?;
-        let playlist_id = playlist_uri
-            .rsplit(':')
-            .next()
-            .context("Invalid Spotify playlist URI")?;
+    async fn rip(&self, uri: &str) -> Result<()> {
+        let (kind, id) = Self::parse_resource_uri(uri)?;
+
+        if kind == ResourceKind::Track {
+            let download_dir = self.make_unique_directory(&self.output_dir.join(&id))?;
+            info!("Ripping track {id} to {}", download_dir.display());
+            if let Err(error) = self.rip_track(&id, &download_dir).await {
+                error!("Failed to rip track {id}: {error:#}");
+            }
+            return Ok(());
+        }
+
+        if kind == ResourceKind::Episode {
+            let download_dir = self.make_unique_directory(&self.output_dir.join(&id))?;
+            info!("Ripping episode {id} to {}", download_dir.display());
+            if let Err(error) = self.rip_episode(&id, &download_dir).await {
+                error!("Failed to rip episode {id}: {error:#}");
+            }
+            return Ok(());
+        }
+
+        let download_dir = self.make_unique_directory(&self.output_dir.join(&id))?;
+        info!("Ripping {uri} to {}", download_dir.display());
 
-        let download_dir = self.make_unique_directory(&self.downloads_dir.join(playlist_id))?;
-        info!("Ripping {playlist_uri} to {}", download_dir.display());
+        if kind == ResourceKind::Show {
+            let episode_ids = self.fetch_show_episodes(&id).await?;
+            return self.rip_episodes(&episode_ids, &download_dir).await;
+        }
+
+        let track_ids = match kind {
+            ResourceKind::Playlist => self.fetch_playlist_tracks(&id).await?,
+            ResourceKind::Album => self.fetch_album_tracks(&id).await?,
+            ResourceKind::Artist => self.fetch_artist_tracks(&id).await?,
+            ResourceKind::Track | ResourceKind::Episode | ResourceKind::Show => unreachable!(),
+        };
 
-        let track_ids = self.fetch_playlist_tracks(playlist_id).await?;
+        self.rip_tracks(&track_ids, &download_dir).await
+    }
 
+    async fn rip_tracks(&self, track_ids: &[String], download_dir: &Path) -> Result<()> {
         let progress = ProgressBar::new(track_ids.len() as u64);
         progress.set_style(
             ProgressStyle::with_template("{pos}/{len} tracks downloaded")
@@ -241,14 +569,53 @@ impl MrRippah {
                 .progress_chars("=> "),
         );
 
-        for track_id in track_ids {
-            if let Err(error) = self.rip_track(&track_id, &download_dir).await {
-                error!("Failed to rip track {track_id}: {error:#}");
-            }
-            progress.inc(1);
-            debug!("Waiting {SUCCESSFUL_DOWNLOAD_DELAY_SECONDS} seconds to start next download");
-            sleep(Duration::from_secs(SUCCESSFUL_DOWNLOAD_DELAY_SECONDS)).await;
-        }
+        futures_util::stream::iter(track_ids)
+            .map(|track_id| {
+                let progress = progress.clone();
+                async move {
+                    if let Err(error) = self.rip_track(track_id, download_dir).await {
+                        error!("Failed to rip track {track_id}: {error:#}");
+                    }
+                    progress.inc(1);
+                    debug!(
+                        "Worker waiting {SUCCESSFUL_DOWNLOAD_DELAY_SECONDS} seconds before its next download"
+                    );
+                    sleep(Duration::from_secs(SUCCESSFUL_DOWNLOAD_DELAY_SECONDS)).await;
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<()>>()
+            .await;
+
+        progress.finish();
+        Ok(())
+    }
+
+    async fn rip_episodes(&self, episode_ids: &[String], download_dir: &Path) -> Result<()> {
+        let progress = ProgressBar::new(episode_ids.len() as u64);
+        progress.set_style(
+            ProgressStyle::with_template("{pos}/{len} episodes downloaded")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+
+        futures_util::stream::iter(episode_ids)
+            .map(|episode_id| {
+                let progress = progress.clone();
+                async move {
+                    if let Err(error) = self.rip_episode(episode_id, download_dir).await {
+                        error!("Failed to rip episode {episode_id}: {error:#}");
+                    }
+                    progress.inc(1);
+                    debug!(
+                        "Worker waiting {SUCCESSFUL_DOWNLOAD_DELAY_SECONDS} seconds before its next download"
+                    );
+                    sleep(Duration::from_secs(SUCCESSFUL_DOWNLOAD_DELAY_SECONDS)).await;
+                }
+            })
+            .buffer_unordered(self.concurrency)
+            .collect::<Vec<()>>()
+            .await;
 
         progress.finish();
         Ok(())
@@ -275,6 +642,56 @@ impl MrRippah {
         Ok(track_ids)
     }
 
+    async fn fetch_album_tracks(&self, album_id: &str) -> Result<Vec<String>> {
+        let mut next_url = Some(format!(
+            "https://api.spotify.com/v1/albums/{album_id}/tracks?market={SPOTIFY_MARKET}"
+        ));
+        let mut track_ids = Vec::new();
+
+        while let Some(url) = next_url {
+            let payload: AlbumTracksResponse = self.spotify_api_request(&url).await?;
+            track_ids.extend(payload.items.into_iter().filter_map(|track| track.id));
+            next_url = payload.next;
+        }
+
+        Ok(track_ids)
+    }
+
+    async fn fetch_artist_tracks(&self, artist_id: &str) -> Result<Vec<String>> {
+        let mut next_url = Some(format!(
+            "https://api.spotify.com/v1/artists/{artist_id}/albums?include_groups=album,single&market={SPOTIFY_MARKET}"
+        ));
+        let mut album_ids = Vec::new();
+
+        while let Some(url) = next_url {
+            let payload: ArtistAlbumsResponse = self.spotify_api_request(&url).await?;
+            album_ids.extend(payload.items.into_iter().map(|album| album.id));
+            next_url = payload.next;
+        }
+
+        let mut track_ids = Vec::new();
+        for album_id in album_ids {
+            track_ids.extend(self.fetch_album_tracks(&album_id).await?);
+        }
+
+        Ok(track_ids)
+    }
+
+    async fn fetch_show_episodes(&self, show_id: &str) -> Result<Vec<String>> {
+        let mut next_url = Some(format!(
+            "https://api.spotify.com/v1/shows/{show_id}/episodes?market={SPOTIFY_MARKET}"
+        ));
+        let mut episode_ids = Vec::new();
+
+        while let Some(url) = next_url {
+            let payload: ShowEpisodesResponse = self.spotify_api_request(&url).await?;
+            episode_ids.extend(payload.items.into_iter().filter_map(|episode| episode.id));
+            next_url = payload.next;
+        }
+
+        Ok(episode_ids)
+    }
+
     async fn rip_track(&self, track_id: &str, download_dir: &Path) -> Result<()> {
         let metadata = self.get_track_metadata(track_id).await?;
         if matches!(metadata.is_playable, Some(false)) {
@@ -282,9 +699,100 @@ impl MrRippah {
             return Ok(());
         }
 
-        let audio_file = self.download_track_audio(track_id).await?;
-        let mp3_path = self.convert_to_mp3(&audio_file, &metadata, download_dir)?;
-        self.write_id3_tags(&mp3_path, &metadata, track_id).await?;
+        let artist = metadata
+            .album
+            .artists
+            .first()
+            .map(|artist| artist.name.clone())
+            .unwrap_or_else(|| "Unknown Artist".to_string());
+
+        let year = parse_release_year(&metadata.album.release_date);
+
+        let Some((format, audio_file)) = self.download_track_audio(track_id).await? else {
+            return Ok(());
+        };
+        let track_path = self.finalize_audio(
+            format,
+            &audio_file,
+            download_dir,
+            PathTemplateVars {
+                artist: &artist,
+                album: &metadata.album.name,
+                track_number: metadata.track_number,
+                disc_number: metadata.disc_number,
+                title: &metadata.name,
+                year,
+            },
+        )?;
+        let artist_name = metadata
+            .artists
+            .first()
+            .map(|artist| artist.name.as_str())
+            .unwrap_or(&artist);
+        let source_uri = format!("spotify:track:{track_id}");
+        self.write_audio_tags(
+            &track_path,
+            AudioTagFields {
+                title: &metadata.name,
+                artist: artist_name,
+                album_artist: metadata.album.artists.first().map(|artist| artist.name.as_str()),
+                album: &metadata.album.name,
+                track_number: metadata.track_number,
+                disc_number: metadata.disc_number,
+                year,
+                isrc: metadata.external_ids.isrc.as_deref(),
+                source_uri: &source_uri,
+                cover_art_url: metadata.album.images.first().map(|image| image.url.as_str()),
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn rip_episode(&self, episode_id: &str, download_dir: &Path) -> Result<()> {
+        let metadata = self.get_episode_metadata(episode_id).await?;
+        if matches!(metadata.is_playable, Some(false)) {
+            debug!("{episode_id} SKIPPING! Episode not playable");
+            return Ok(());
+        }
+
+        let year = parse_release_year(&metadata.release_date);
+
+        let Some((format, audio_file)) = self.download_episode_audio(episode_id).await? else {
+            return Ok(());
+        };
+        let track_path = self.finalize_audio(
+            format,
+            &audio_file,
+            download_dir,
+            PathTemplateVars {
+                artist: &metadata.show.name,
+                album: &metadata.show.name,
+                track_number: 1,
+                disc_number: 1,
+                title: &metadata.name,
+                year,
+            },
+        )?;
+
+        let source_uri = format!("spotify:episode:{episode_id}");
+        self.write_audio_tags(
+            &track_path,
+            AudioTagFields {
+                title: &metadata.name,
+                artist: &metadata.show.name,
+                album_artist: Some(&metadata.show.name),
+                album: &metadata.show.name,
+                track_number: 1,
+                disc_number: 1,
+                year,
+                isrc: None,
+                source_uri: &source_uri,
+                cover_art_url: metadata.show.images.first().map(|image| image.url.as_str()),
+            },
+        )
+        .await?;
 
         Ok(())
     }
@@ -302,19 +810,46 @@ impl MrRippah {
             .await
             .context("Unable to obtain Spotify token")?;
 
-        let response = self
-            .http_client
-            .get(url)
-            .bearer_auth(token.access_token)
-            .send()
-            .context("Spotify API request failed")?;
-        let status = response.status();
-        if !status.is_success() {
-            anyhow::bail!("Spotify API error: {status}");
+        for attempt in 0..=SPOTIFY_API_MAX_RETRIES {
+            let response = self
+                .http_client
+                .get(&url)
+                .bearer_auth(&token.access_token)
+                .send()
+                .context("Spotify API request failed")?;
+            let status = response.status();
+
+            if status.is_success() {
+                return Ok(response
+                    .json()
+                    .context("Unable to parse Spotify API response")?);
+            }
+
+            if attempt == SPOTIFY_API_MAX_RETRIES {
+                anyhow::bail!("Spotify API error: {status}");
+            }
+
+            let delay = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                let retry_after = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or(SPOTIFY_RATE_LIMIT_DEFAULT_DELAY_SECONDS);
+                warn!("Spotify rate limited us, waiting {retry_after}s before retrying");
+                retry_after
+            } else if status.is_server_error() {
+                let backoff = SPOTIFY_RATE_LIMIT_DEFAULT_DELAY_SECONDS * 2u64.pow(attempt);
+                warn!("Spotify API error {status}, retrying in {backoff}s (attempt {}/{SPOTIFY_API_MAX_RETRIES})", attempt + 1);
+                backoff
+            } else {
+                anyhow::bail!("Spotify API error: {status}");
+            };
+
+            sleep(Duration::from_secs(delay)).await;
         }
-        Ok(response
-            .json()
-            .context("Unable to parse Spotify API response")?)
+
+        unreachable!()
     }
 
     async fn get_track_metadata(&self, track_id: &str) -> Result<TrackMetadata> {
@@ -322,21 +857,81 @@ impl MrRippah {
         self.spotify_api_request(&endpoint).await
     }
 
-    async fn download_track_audio(&self, track_id: &str) -> Result<PathBuf> {
+    async fn get_episode_metadata(&self, episode_id: &str) -> Result<EpisodeMetadata> {
+        let endpoint = format!("episodes/{episode_id}?market={SPOTIFY_MARKET}");
+        self.spotify_api_request(&endpoint).await
+    }
+
+    async fn download_track_audio(
+        &self,
+        track_id: &str,
+    ) -> Result<Option<(AudioFileFormat, PathBuf)>> {
         let spotify_id = SpotifyId::from_base62(track_id).context("Invalid track identifier")?;
         let track = Track::get(&self.session, &spotify_id)
             .await
             .context("Unable to fetch track metadata")?;
 
-        let (format, file_id) = [
-            AudioFileFormat::OGG_VORBIS_320,
-            AudioFileFormat::OGG_VORBIS_160,
-            AudioFileFormat::OGG_VORBIS_96,
-        ]
-        .into_iter()
-        .find_map(|format| track.files.get(&format).copied().map(|file| (format, file)))
-        .context("No supported audio files available for track")?;
+        if is_restricted_for_market(&track.restrictions, SPOTIFY_MARKET) {
+            debug!(
+                "{track_id} SKIPPING! Not available in {SPOTIFY_MARKET} per catalogue restrictions"
+            );
+            return Ok(None);
+        }
+
+        let (format, file_id) = self
+            .quality_preset
+            .format_priority()
+            .iter()
+            .copied()
+            .find_map(|format| track.files.get(&format).copied().map(|file| (format, file)))
+            .context("No supported audio files available for track")?;
+
+        let path = self
+            .download_and_decrypt(spotify_id, format, file_id, track_id)
+            .await?;
+        Ok(Some((format, path)))
+    }
+
+    async fn download_episode_audio(
+        &self,
+        episode_id: &str,
+    ) -> Result<Option<(AudioFileFormat, PathBuf)>> {
+        let spotify_id = SpotifyId::from_base62(episode_id).context("Invalid episode identifier")?;
+        let episode = Episode::get(&self.session, &spotify_id)
+            .await
+            .context("Unable to fetch episode metadata")?;
+
+        if is_restricted_for_market(&episode.restrictions, SPOTIFY_MARKET) {
+            debug!(
+                "{episode_id} SKIPPING! Not available in {SPOTIFY_MARKET} per catalogue restrictions"
+            );
+            return Ok(None);
+        }
+
+        let (format, file_id) = self
+            .quality_preset
+            .format_priority()
+            .iter()
+            .copied()
+            .find_map(|format| episode.files.get(&format).copied().map(|file| (format, file)))
+            .context("No supported audio files available for episode")?;
+
+        let path = self
+            .download_and_decrypt(spotify_id, format, file_id, episode_id)
+            .await?;
+        Ok(Some((format, path)))
+    }
 
+    /// Fetches and decrypts the audio behind a librespot `FileId`, writing it
+    /// to a temporary file. Shared by tracks and podcast episodes, which
+    /// expose their audio the same way despite differing metadata sources.
+    async fn download_and_decrypt(
+        &self,
+        spotify_id: SpotifyId,
+        format: AudioFileFormat,
+        file_id: librespot::core::FileId,
+        item_id: &str,
+    ) -> Result<PathBuf> {
         let bytes_per_second = stream_data_rate(format);
 
         let encrypted = SpotifyAudioFile::open(&self.session, file_id, bytes_per_second)
@@ -346,7 +941,7 @@ impl MrRippah {
         let key = match self.session.audio_key().request(spotify_id, file_id).await {
             Ok(key) => Some(key),
             Err(error) => {
-                warn!("Unable to load audio key for {track_id}: {error}");
+                warn!("Unable to load audio key for {item_id}: {error}");
                 None
             }
         };
@@ -359,34 +954,39 @@ impl MrRippah {
         Ok(path)
     }
 
-    fn convert_to_mp3(
+    /// Delivers the decrypted audio to its final location, either moving the
+    /// native-format file into place unchanged or transcoding it to MP3, depending on
+    /// whether the active quality preset already matches what Spotify sent.
+    fn finalize_audio(
         &self,
-        ogg_path: &Path,
-        metadata: &TrackMetadata,
+        format: AudioFileFormat,
+        decrypted_path: &Path,
         download_dir: &Path,
+        vars: PathTemplateVars,
     ) -> Result<PathBuf> {
-        let artist = metadata
-            .album
-            .artists
-            .first()
-            .map(|artist| artist.name.clone())
-            .unwrap_or_else(|| "Unknown Artist".to_string());
-        let album = metadata.album.name.clone();
-        let track_name = metadata.name.clone();
+        let extension = self.quality_preset.delivery_extension(format);
 
-        let track_path = download_dir
-            .join(&artist)
-            .join(&album)
-            .join(format!("{0:02} - {track_name}.mp3", metadata.track_number));
+        let rendered = render_path_template(&self.path_template, &vars);
+        let track_path = download_dir.join(format!("{rendered}.{extension}"));
         if let Some(parent) = track_path.parent() {
             fs::create_dir_all(parent).context("Unable to create track directory structure")?;
         }
 
+        if native_extension(format) == extension {
+            fs::rename(decrypted_path, &track_path).or_else(|_| {
+                fs::copy(decrypted_path, &track_path)
+                    .map(|_| ())
+                    .and_then(|()| fs::remove_file(decrypted_path))
+            })
+            .context("Unable to move downloaded audio into place")?;
+            return Ok(track_path);
+        }
+
         let status = Command::new("ffmpeg")
             .args([
                 "-y",
                 "-i",
-                ogg_path.to_str().context("Invalid temporary audio path")?,
+                decrypted_path.to_str().context("Invalid temporary audio path")?,
                 "-codec:a",
                 "libmp3lame",
                 "-qscale:a",
@@ -396,6 +996,8 @@ impl MrRippah {
             .status()
             .context("Failed to spawn ffmpeg")?;
 
+        fs::remove_file(decrypted_path).ok();
+
         if !status.success() {
             anyhow::bail!("ffmpeg failed with status {status}");
         }
@@ -403,62 +1005,58 @@ impl MrRippah {
         Ok(track_path)
     }
 
-    async fn write_id3_tags(
-        &self,
-        track_path: &Path,
-        metadata: &TrackMetadata,
-        track_id: &str,
-    ) -> Result<()> {
-        let mut tag = Tag::new();
-        tag.set_title(&metadata.name);
-        if let Some(artist) = metadata.artists.first() {
-            tag.set_artist(&artist.name);
+    /// Tags the finished audio file through lofty's format-agnostic tag API,
+    /// so ID3v2 (MP3), Vorbis comments (OGG/FLAC), and any other container
+    /// lofty understands all get the same metadata written the same way.
+    async fn write_audio_tags(&self, track_path: &Path, fields: AudioTagFields<'_>) -> Result<()> {
+        let mut tagged_file = Probe::open(track_path)
+            .context("Unable to probe output file")?
+            .read()
+            .context("Unable to read tag container")?;
+
+        if tagged_file.primary_tag().is_none() {
+            tagged_file.insert_tag(Tag::new(tagged_file.primary_tag_type()));
         }
-        if let Some(album_artist) = metadata.album.artists.first() {
-            tag.set_album_artist(&album_artist.name);
+        let tag = tagged_file
+            .primary_tag_mut()
+            .expect("a primary tag was just inserted if one didn't already exist");
+
+        tag.set_title(fields.title.to_string());
+        tag.set_artist(fields.artist.to_string());
+        if let Some(album_artist) = fields.album_artist {
+            tag.insert_text(ItemKey::AlbumArtist, album_artist.to_string());
         }
-        tag.set_album(&metadata.album.name);
-        tag.set_track(metadata.track_number as u32);
-        tag.set_disc(metadata.disc_number as u32);
-        tag.set_year(
-            metadata.album.release_date[0..4]
-                .parse::<i32>()
-                .unwrap_or_default(),
-        );
-        if let Some(isrc) = &metadata.external_ids.isrc {
-            tag.add_frame(id3::Frame::with_content(
-                "TSRC",
-                id3::Content::Text(isrc.clone()),
-            ));
+        tag.set_album(fields.album.to_string());
+        tag.set_track(fields.track_number);
+        tag.set_disk(fields.disc_number);
+        if let Some(year) = fields.year {
+            tag.set_year(year);
         }
+        if let Some(isrc) = fields.isrc {
+            tag.insert_text(ItemKey::Isrc, isrc.to_string());
+        }
+        tag.insert_text(ItemKey::Comment, fields.source_uri.to_string());
 
-        tag.add_frame(id3::Frame::with_content(
-            "TXXX",
-            id3::Content::Text(format!("spotify:track:{track_id}")),
-        ));
-
-        if let Some(image) = metadata.album.images.first() {
+        if let Some(url) = fields.cover_art_url {
             let response = self
                 .http_client
-                .get(&image.url)
+                .get(url)
                 .send()
-                .context("Unable to download album art")?;
+                .context("Unable to download cover art")?;
             if response.status().is_success() {
-                let bytes = response.bytes().context("Unable to read album art bytes")?;
-                tag.add_frame(id3::Frame::with_content(
-                    "APIC",
-                    id3::Content::Picture(id3::frame::Picture {
-                        mime_type: "image/jpeg".to_string(),
-                        picture_type: PictureType::CoverFront,
-                        description: String::from("Cover"),
-                        data: bytes.to_vec(),
-                    }),
+                let bytes = response.bytes().context("Unable to read cover art bytes")?;
+                tag.push_picture(Picture::new_unchecked(
+                    PictureType::CoverFront,
+                    Some(MimeType::Jpeg),
+                    None,
+                    bytes.to_vec(),
                 ));
             }
         }
 
-        tag.write_to_path(track_path, Version::Id3v24)
-            .context("Unable to write ID3 tags")?;
+        tagged_file
+            .save_to_path(track_path, WriteOptions::default())
+            .context("Unable to write audio tags")?;
 
         Ok(())
     }
@@ -486,26 +1084,63 @@ impl MrRippah {
         unreachable!()
     }
 
-    fn normalise_playlist_uri(input: &str) -> Result<String> {
-        if input.starts_with("spotify:playlist:") {
-            return Ok(input.to_string());
+    /// Parses a `spotify:<kind>:<id>` URI or an `open.spotify.com/<kind>/<id>`
+    /// URL into the resource kind it names and its bare Spotify ID.
+    fn parse_resource_uri(input: &str) -> Result<(ResourceKind, String)> {
+        if let Some(rest) = input.strip_prefix("spotify:") {
+            let mut parts = rest.splitn(2, ':');
+            let segment = parts.next().unwrap_or_default();
+            if let Some(id) = parts.next() {
+                if let Some(kind) = ResourceKind::ALL.iter().find(|kind| kind.uri_segment() == segment) {
+                    return Ok((*kind, id.to_string()));
+                }
+            }
         }
 
         if let Ok(url) = Url::parse(input) {
             if url.domain() == Some("open.spotify.com") {
                 if let Some(segments) = url.path_segments() {
                     let segments: Vec<_> = segments.collect();
-                    if segments.len() >= 2 && segments[0] == "playlist" {
-                        return Ok(format!("spotify:playlist:{}", segments[1]));
+                    if segments.len() >= 2 {
+                        if let Some(kind) =
+                            ResourceKind::ALL.iter().find(|kind| kind.uri_segment() == segments[0])
+                        {
+                            return Ok((*kind, segments[1].to_string()));
+                        }
                     }
                 }
             }
         }
 
-        anyhow::bail!("Invalid Spotify playlist URI: {input}");
+        anyhow::bail!("Invalid or unsupported Spotify URI: {input}");
     }
 }
 
+/// Evaluates librespot's per-catalogue `Restriction` entries against a market,
+/// rather than trusting the Web API's often-absent `is_playable` field. A
+/// market is restricted if it appears in a forbidden list, or if an allowed
+/// list exists and excludes it.
+fn is_restricted_for_market(restrictions: &[Restriction], market: &str) -> bool {
+    restrictions.iter().any(|restriction| {
+        let forbidden = restriction
+            .countries_forbidden
+            .as_deref()
+            .is_some_and(|codes| country_codes(codes).any(|code| code == market));
+        let excluded_from_allowed = restriction
+            .countries_allowed
+            .as_deref()
+            .is_some_and(|codes| !codes.is_empty() && country_codes(codes).all(|code| code != market));
+
+        forbidden || excluded_from_allowed
+    })
+}
+
+/// Splits a Spotify restriction country list (e.g. `"USCAGB"`) into its
+/// two-letter country codes.
+fn country_codes(codes: &str) -> impl Iterator<Item = &str> {
+    codes.as_bytes().chunks(2).filter_map(|chunk| std::str::from_utf8(chunk).ok())
+}
+
 fn stream_data_rate(format: AudioFileFormat) -> usize {
     match format {
         AudioFileFormat::OGG_VORBIS_96 => 12 * 1024,
@@ -552,8 +1187,28 @@ async fn main() -> Result<()> {
         .filter_level(filter)
         .init();
 
-    let app = MrRippah::new(cli.clear_existing_credentials, filter).await?;
-    if let Err(error) = app.rip_playlist(&cli.uri).await {
+    let config = RippahConfig::load(cli.config.as_deref())?;
+    let quality = cli
+        .quality
+        .or(config.quality)
+        .unwrap_or(QualityPreset::BestBitrate);
+    let concurrency = cli.concurrency.or(config.concurrency).unwrap_or(4);
+    let output_dir = cli.output_dir.or(config.output_dir);
+    let path_template = cli
+        .path_template
+        .or(config.path_template)
+        .unwrap_or_else(|| DEFAULT_PATH_TEMPLATE.to_string());
+
+    let app = MrRippah::new(
+        cli.clear_existing_credentials,
+        filter,
+        quality,
+        concurrency,
+        output_dir,
+        path_template,
+    )
+    .await?;
+    if let Err(error) = app.rip(&cli.uri).await {
         error!("{error:#}");
         std::process::exit(1);
     }